@@ -0,0 +1,365 @@
+use bytes::{Buf, Bytes, BytesMut};
+use futures_lite::prelude::*;
+use http::HeaderMap;
+use std::borrow::BorrowMut;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// How the body's boundary is determined, per RFC 7230 section 3.3.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BodyMode {
+    /// `Content-Length: N` - exactly `N` bytes follow the head.
+    Length(usize),
+    /// `Transfer-Encoding: chunked` - size-prefixed chunks, terminated by a zero-size chunk.
+    Chunked,
+}
+
+impl BodyMode {
+    /// Determines the body framing from a decoded head's headers.
+    ///
+    /// Returns `Ok(None)` when neither header is present, i.e. the message has no body.
+    pub fn from_headers(headers: &HeaderMap) -> io::Result<Option<Self>> {
+        if let Some(value) = headers.get(http::header::TRANSFER_ENCODING) {
+            let value = value
+                .to_str()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid Transfer-Encoding"))?;
+            if value
+                .rsplit(',')
+                .next()
+                .map(|token| token.trim().eq_ignore_ascii_case("chunked"))
+                .unwrap_or(false)
+            {
+                return Ok(Some(BodyMode::Chunked));
+            }
+        }
+        if let Some(value) = headers.get(http::header::CONTENT_LENGTH) {
+            let value = value
+                .to_str()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length"))?;
+            let len = value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length"))?;
+            return Ok(Some(BodyMode::Length(len)));
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BodyDecoder {
+    mode: BodyMode,
+    max_body_size: usize,
+}
+
+impl BodyDecoder {
+    pub fn new(mode: BodyMode, max_body_size: usize) -> io::Result<Self> {
+        if let BodyMode::Length(len) = mode {
+            if len > max_body_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "content length exceeds max body size",
+                ));
+            }
+        }
+        Ok(Self { mode, max_body_size })
+    }
+
+    /// Starts decoding a body from `transport`. `leading` is any bytes already read
+    /// past the head terminator (e.g. by a bulk-reading head decoder) that belong
+    /// to this body and must be consumed before reading more from the transport.
+    pub fn decode<T: AsyncRead + Unpin, R: BorrowMut<T>>(
+        self,
+        transport: R,
+        leading: Bytes,
+    ) -> BodyDecode<T, R> {
+        let state = match self.mode {
+            BodyMode::Length(remaining) => State::Length(remaining),
+            BodyMode::Chunked => State::ChunkSize,
+        };
+        BodyDecode {
+            transport: Some(transport),
+            decoder: self,
+            buffer: BytesMut::from(leading.as_ref()),
+            state,
+            emitted: 0,
+            p: PhantomData,
+        }
+    }
+}
+
+impl Default for BodyDecoder {
+    fn default() -> Self {
+        Self {
+            mode: BodyMode::Length(0),
+            max_body_size: 2 * 1024 * 1024,
+        }
+    }
+}
+
+const READ_SIZE: usize = 8192;
+
+#[derive(Copy, Clone, Debug)]
+enum State {
+    Length(usize),
+    ChunkSize,
+    ChunkData(usize),
+    ChunkDataCrlf,
+    Trailer,
+    Done,
+}
+
+/// Streams a message body from `transport`, framed according to the [`BodyMode`]
+/// the decoder was built with. Stops exactly at the body boundary so the transport
+/// can be reused for the next message.
+#[pin_project::pin_project]
+pub struct BodyDecode<T: AsyncRead + Unpin, R: BorrowMut<T>> {
+    transport: Option<R>,
+    decoder: BodyDecoder,
+    buffer: BytesMut,
+    state: State,
+    emitted: usize,
+    p: PhantomData<*const T>,
+}
+
+impl<T: AsyncRead + Unpin, R: BorrowMut<T>> BodyDecode<T, R> {
+    /// Hands the transport back along with any bytes already read past the body
+    /// boundary (the start of the next pipelined message). Only meaningful once
+    /// the stream has reported `None`.
+    pub fn into_parts(mut self) -> (Option<R>, Bytes) {
+        (self.transport.take(), std::mem::take(&mut self.buffer).freeze())
+    }
+
+    fn fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        let mut chunk = [0u8; READ_SIZE];
+        let transport = Pin::new(self.transport.as_mut().expect("body already consumed").borrow_mut());
+        match transport.poll_read(cx, &mut chunk) {
+            Poll::Ready(Ok(0)) => Poll::Ready(Ok(false)),
+            Poll::Ready(Ok(n)) => {
+                self.buffer.extend_from_slice(&chunk[..n]);
+                Poll::Ready(Ok(true))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin, R: BorrowMut<T>> Stream for BodyDecode<T, R> {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.state {
+                State::Done => return Poll::Ready(None),
+                State::Length(0) => {
+                    self.state = State::Done;
+                    return Poll::Ready(None);
+                }
+                State::Length(remaining) => {
+                    if self.buffer.is_empty() {
+                        match self.fill(cx) {
+                            Poll::Ready(Ok(true)) => continue,
+                            Poll::Ready(Ok(false)) => {
+                                return Poll::Ready(Some(Err(io::Error::new(
+                                    io::ErrorKind::UnexpectedEof,
+                                    "connection closed before Content-Length bytes were read",
+                                ))))
+                            }
+                            Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                            Poll::Pending => return Poll::Pending,
+                        }
+                    }
+                    let take = self.buffer.len().min(remaining);
+                    let bytes = self.buffer.split_to(take).freeze();
+                    self.state = State::Length(remaining - take);
+                    return Poll::Ready(Some(Ok(bytes)));
+                }
+                State::ChunkSize => match find_crlf(&self.buffer) {
+                    Some(pos) => {
+                        let line = self.buffer.split_to(pos + 2);
+                        let size = match parse_chunk_size(&line[..pos]) {
+                            Ok(size) => size,
+                            Err(err) => {
+                                self.state = State::Done;
+                                return Poll::Ready(Some(Err(err)));
+                            }
+                        };
+                        if self.emitted + size > self.decoder.max_body_size {
+                            self.state = State::Done;
+                            return Poll::Ready(Some(Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "chunked body exceeds max body size",
+                            ))));
+                        }
+                        self.state = if size == 0 {
+                            State::Trailer
+                        } else {
+                            State::ChunkData(size)
+                        };
+                    }
+                    None => match self.fill(cx) {
+                        Poll::Ready(Ok(true)) => continue,
+                        Poll::Ready(Ok(false)) => {
+                            return Poll::Ready(Some(Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "connection closed mid chunk size",
+                            ))))
+                        }
+                        Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                        Poll::Pending => return Poll::Pending,
+                    },
+                },
+                State::ChunkData(remaining) => {
+                    if self.buffer.is_empty() {
+                        match self.fill(cx) {
+                            Poll::Ready(Ok(true)) => continue,
+                            Poll::Ready(Ok(false)) => {
+                                return Poll::Ready(Some(Err(io::Error::new(
+                                    io::ErrorKind::UnexpectedEof,
+                                    "connection closed mid chunk data",
+                                ))))
+                            }
+                            Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                            Poll::Pending => return Poll::Pending,
+                        }
+                    }
+                    let take = self.buffer.len().min(remaining);
+                    let bytes = self.buffer.split_to(take).freeze();
+                    self.emitted += take;
+                    self.state = if remaining - take == 0 {
+                        State::ChunkDataCrlf
+                    } else {
+                        State::ChunkData(remaining - take)
+                    };
+                    return Poll::Ready(Some(Ok(bytes)));
+                }
+                State::ChunkDataCrlf => {
+                    if self.buffer.len() < 2 {
+                        match self.fill(cx) {
+                            Poll::Ready(Ok(true)) => continue,
+                            Poll::Ready(Ok(false)) => {
+                                return Poll::Ready(Some(Err(io::Error::new(
+                                    io::ErrorKind::UnexpectedEof,
+                                    "connection closed before chunk terminator",
+                                ))))
+                            }
+                            Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                            Poll::Pending => return Poll::Pending,
+                        }
+                    }
+                    if &self.buffer[..2] != b"\r\n" {
+                        self.state = State::Done;
+                        return Poll::Ready(Some(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "missing chunk terminator",
+                        ))));
+                    }
+                    self.buffer.advance(2);
+                    self.state = State::ChunkSize;
+                }
+                State::Trailer => match trailer_end(&self.buffer) {
+                    Some(len) => {
+                        self.buffer.advance(len);
+                        self.state = State::Done;
+                        return Poll::Ready(None);
+                    }
+                    None => match self.fill(cx) {
+                        Poll::Ready(Ok(true)) => continue,
+                        Poll::Ready(Ok(false)) => {
+                            return Poll::Ready(Some(Err(io::Error::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "connection closed mid chunk trailer",
+                            ))))
+                        }
+                        Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                        Poll::Pending => return Poll::Pending,
+                    },
+                },
+            }
+        }
+    }
+}
+
+fn parse_chunk_size(line: &[u8]) -> io::Result<usize> {
+    let line = line.split(|&b| b == b';').next().unwrap_or(line);
+    let line = std::str::from_utf8(line)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed chunk size"))?;
+    usize::from_str_radix(line.trim(), 16)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed chunk size"))
+}
+
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+fn trailer_end(buf: &[u8]) -> Option<usize> {
+    if buf.len() >= 2 && &buf[..2] == b"\r\n" {
+        return Some(2);
+    }
+    buf.windows(4).position(|w| w == b"\r\n\r\n").map(|pos| pos + 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::future::block_on;
+    use futures_lite::io::Cursor;
+    use futures_lite::StreamExt;
+
+    async fn collect(mut decode: impl Stream<Item = io::Result<Bytes>> + Unpin) -> Vec<u8> {
+        let mut out = Vec::new();
+        while let Some(chunk) = decode.next().await {
+            out.extend_from_slice(&chunk.unwrap());
+        }
+        out
+    }
+
+    #[test]
+    fn length_delimited() {
+        block_on(async {
+            let transport = Cursor::new(b"hello world".to_vec());
+            let decoder = BodyDecoder::new(BodyMode::Length(5), 1024).unwrap();
+            let out = collect(decoder.decode(transport, Bytes::new())).await;
+            assert_eq!(out, b"hello");
+        })
+    }
+
+    #[test]
+    fn chunked() {
+        block_on(async {
+            let transport = Cursor::new(b"4\r\nwiki\r\n5\r\npedia\r\n0\r\n\r\n".to_vec());
+            let decoder = BodyDecoder::new(BodyMode::Chunked, 1024).unwrap();
+            let out = collect(decoder.decode(transport, Bytes::new())).await;
+            assert_eq!(out, b"wikipedia");
+        })
+    }
+
+    #[test]
+    fn chunked_with_trailer() {
+        block_on(async {
+            let transport = Cursor::new(b"3\r\nfoo\r\n0\r\nX-Trailer: x\r\n\r\n".to_vec());
+            let decoder = BodyDecoder::new(BodyMode::Chunked, 1024).unwrap();
+            let out = collect(decoder.decode(transport, Bytes::new())).await;
+            assert_eq!(out, b"foo");
+        })
+    }
+
+    #[test]
+    fn malformed_chunk_size() {
+        block_on(async {
+            let transport = Cursor::new(b"zz\r\n".to_vec());
+            let decoder = BodyDecoder::new(BodyMode::Chunked, 1024).unwrap();
+            let mut decode = decoder.decode(transport, Bytes::new());
+            let err = decode.next().await.unwrap().unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        })
+    }
+
+    #[test]
+    fn content_length_over_cap_is_rejected_up_front() {
+        assert!(BodyDecoder::new(BodyMode::Length(2048), 1024).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use http::header::{HeaderName, CONNECTION, TRANSFER_ENCODING};
+use http::request::Parts as RequestParts;
+use http::response::Parts as ResponseParts;
+use http::{HeaderMap, Method, Version};
+use std::io;
+
+/// HTTP/1 connection-management semantics derived from a decoded message head,
+/// mirroring the checks actix's `HttpRequest` runs before deciding whether to
+/// reuse, hand off, or chunk-decode a connection.
+pub trait ConnectionExt {
+    /// Whether the transport should be kept open for another message on completion,
+    /// per RFC 7230 section 6.3: true for HTTP/1.1 unless `Connection: close` is
+    /// present, true for HTTP/1.0 only when `Connection: keep-alive` is present.
+    fn keep_alive(&self) -> bool;
+
+    /// Whether the connection is being switched to another protocol: `Connection: upgrade`,
+    /// or - for requests - the `CONNECT` method.
+    fn is_upgrade(&self) -> bool;
+
+    /// Whether the body is framed with `Transfer-Encoding: chunked`.
+    ///
+    /// Errors if a `chunked` token is present anywhere but last.
+    fn chunked(&self) -> io::Result<bool>;
+}
+
+impl ConnectionExt for RequestParts {
+    fn keep_alive(&self) -> bool {
+        keep_alive(self.version, &self.headers)
+    }
+
+    fn is_upgrade(&self) -> bool {
+        self.method == Method::CONNECT || has_token(&self.headers, CONNECTION, "upgrade")
+    }
+
+    fn chunked(&self) -> io::Result<bool> {
+        chunked(&self.headers)
+    }
+}
+
+impl ConnectionExt for ResponseParts {
+    fn keep_alive(&self) -> bool {
+        keep_alive(self.version, &self.headers)
+    }
+
+    fn is_upgrade(&self) -> bool {
+        has_token(&self.headers, CONNECTION, "upgrade")
+    }
+
+    fn chunked(&self) -> io::Result<bool> {
+        chunked(&self.headers)
+    }
+}
+
+fn keep_alive(version: Version, headers: &HeaderMap) -> bool {
+    match version {
+        Version::HTTP_11 => !has_token(headers, CONNECTION, "close"),
+        _ => has_token(headers, CONNECTION, "keep-alive"),
+    }
+}
+
+fn chunked(headers: &HeaderMap) -> io::Result<bool> {
+    let tokens = comma_tokens(headers, TRANSFER_ENCODING)?;
+    match tokens.iter().position(|token| token.eq_ignore_ascii_case("chunked")) {
+        Some(pos) if pos == tokens.len() - 1 => Ok(true),
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "chunked transfer-coding must be last",
+        )),
+        None => Ok(false),
+    }
+}
+
+fn has_token(headers: &HeaderMap, name: HeaderName, token: &str) -> bool {
+    comma_tokens(headers, name)
+        .map(|tokens| tokens.iter().any(|t| t.eq_ignore_ascii_case(token)))
+        .unwrap_or(false)
+}
+
+fn comma_tokens(headers: &HeaderMap, name: HeaderName) -> io::Result<Vec<&str>> {
+    headers
+        .get_all(name)
+        .iter()
+        .map(|value| {
+            value
+                .to_str()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid header value"))
+        })
+        .collect::<io::Result<Vec<_>>>()
+        .map(|values| {
+            values
+                .into_iter()
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .filter(|token| !token.is_empty())
+                .collect()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConnectionExt;
+    use http::{Method, Request, Response, Version};
+
+    fn request(version: Version, method: Method, headers: &[(&str, &str)]) -> http::request::Parts {
+        let mut builder = Request::builder().method(method).version(version);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    fn response(version: Version, headers: &[(&str, &str)]) -> http::response::Parts {
+        let mut builder = Response::builder().version(version);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn http11_keeps_alive_by_default() {
+        let parts = request(Version::HTTP_11, Method::GET, &[]);
+        assert!(parts.keep_alive());
+    }
+
+    #[test]
+    fn http11_connection_close_ends_the_connection() {
+        let parts = request(Version::HTTP_11, Method::GET, &[("Connection", "close")]);
+        assert!(!parts.keep_alive());
+    }
+
+    #[test]
+    fn http10_requires_explicit_keep_alive() {
+        let parts = request(Version::HTTP_10, Method::GET, &[]);
+        assert!(!parts.keep_alive());
+        let parts = request(Version::HTTP_10, Method::GET, &[("Connection", "keep-alive")]);
+        assert!(parts.keep_alive());
+    }
+
+    #[test]
+    fn connect_method_is_an_upgrade() {
+        let parts = request(Version::HTTP_11, Method::CONNECT, &[]);
+        assert!(parts.is_upgrade());
+    }
+
+    #[test]
+    fn connection_upgrade_header_is_an_upgrade() {
+        let parts = response(Version::HTTP_11, &[("Connection", "Upgrade")]);
+        assert!(parts.is_upgrade());
+    }
+
+    #[test]
+    fn chunked_transfer_encoding_is_detected() {
+        let parts = request(Version::HTTP_11, Method::POST, &[("Transfer-Encoding", "gzip, chunked")]);
+        assert!(parts.chunked().unwrap());
+    }
+
+    #[test]
+    fn chunked_not_last_is_an_error() {
+        let parts = request(Version::HTTP_11, Method::POST, &[("Transfer-Encoding", "chunked, gzip")]);
+        assert!(parts.chunked().is_err());
+    }
+
+    #[test]
+    fn no_transfer_encoding_is_not_chunked() {
+        let parts = request(Version::HTTP_11, Method::GET, &[]);
+        assert!(!parts.chunked().unwrap());
+    }
+}
@@ -3,6 +3,7 @@ use futures_lite::prelude::*;
 use http::header::HeaderName;
 use http::{HeaderValue, Method, Request, Uri, Version};
 use std::borrow::BorrowMut;
+use std::io;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::task::{Context, Poll};
@@ -11,21 +12,48 @@ use std::task::{Context, Poll};
 pub struct RequestHeadDecoder {
     max_head_size: usize,
     max_headers: usize,
+    tolerate_leading_blank_lines: bool,
 }
 
 impl RequestHeadDecoder {
+    /// Whether to silently skip blank lines (bare `\r\n`/`\n`) preceding the request
+    /// line, as actix's `consume_leading_lines` does, instead of rejecting them as a
+    /// malformed head. Off by default; strict callers can leave this unset.
+    ///
+    /// Buggy clients occasionally send these, as can a previous pipelined request
+    /// whose trailing CRLF is mistaken for part of the next message.
+    pub fn tolerate_leading_blank_lines(mut self, value: bool) -> Self {
+        self.tolerate_leading_blank_lines = value;
+        self
+    }
+
     pub fn decode<T: AsyncRead + Unpin, R: BorrowMut<T>>(
         self,
         transport: R,
     ) -> RequestHeadDecode<T, R> {
+        self.decode_with_leading(transport, Vec::new())
+    }
+
+    /// Like [`decode`](Self::decode), but seeds the scan buffer with `leading` bytes
+    /// already read from the transport (e.g. the tail of a previous pipelined
+    /// message) before reading any further.
+    pub fn decode_with_leading<T: AsyncRead + Unpin, R: BorrowMut<T>>(
+        self,
+        transport: R,
+        mut leading: Vec<u8>,
+    ) -> RequestHeadDecode<T, R> {
+        leading.reserve(INITIAL_READ_SIZE.min(self.max_head_size));
         RequestHeadDecode {
-            buffer: Vec::with_capacity(self.max_head_size),
+            buffer: leading,
+            scanned: 0,
+            read_buf: vec![0u8; INITIAL_READ_SIZE.min(self.max_head_size)].into_boxed_slice(),
             transport: Some(transport),
             decoder: self,
-            completion: 0,
+            done: false,
             p: Default::default(),
         }
     }
+
     pub fn decode_ref<T: AsyncRead + Unpin>(
         self,
         transport: &mut T,
@@ -39,50 +67,107 @@ impl Default for RequestHeadDecoder {
         Self {
             max_head_size: 8192,
             max_headers: 128,
+            tolerate_leading_blank_lines: false,
         }
     }
 }
 
+/// Bytes read in a single bulk read while scanning for the head terminator.
+const INITIAL_READ_SIZE: usize = 8192;
+const TERMINATOR: &[u8; 4] = b"\r\n\r\n";
+/// Leading bytes of the HTTP/2 client connection preface (RFC 7540 section 3.5),
+/// before the fixed `\r\n\r\nSM\r\n\r\n` that follows it.
+const HTTP2_PREFACE_PREFIX: &[u8; 14] = b"PRI * HTTP/2.0";
+
+/// The outcome of decoding a message head: either a parsed HTTP/1 request, or
+/// detection of an HTTP/2 client preface that the caller should hand off to an
+/// HTTP/2 implementation instead.
+#[derive(Debug)]
+pub enum DecodedHead {
+    /// A parsed HTTP/1 request, plus any bytes read past the head terminator
+    /// (the start of the body) that the caller must not discard.
+    Http1(Box<Request<()>>, Vec<u8>),
+    /// The connection opened with the HTTP/2 client preface instead of an HTTP/1
+    /// request line. Carries every byte read so far, including the preface itself.
+    Http2Preface(Vec<u8>),
+}
+
 #[pin_project::pin_project]
 pub struct RequestHeadDecode<T: AsyncRead + Unpin, R: BorrowMut<T>> {
     buffer: Vec<u8>,
+    /// offset up to which `buffer` was already scanned for `TERMINATOR`, minus the
+    /// overlap needed to catch a terminator split across two reads
+    scanned: usize,
+    read_buf: Box<[u8]>,
     transport: Option<R>,
     decoder: RequestHeadDecoder,
-    completion: usize,
+    done: bool,
     p: PhantomData<*const T>,
 }
 
 impl<T: AsyncRead + Unpin, R: BorrowMut<T>> Future for RequestHeadDecode<T, R> {
-    type Output = anyhow::Result<(R, Request<()>)>;
+    type Output = anyhow::Result<(R, DecodedHead)>;
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        assert_ne!(self.completion, usize::MAX);
-        const END: &[u8; 4] = b"\r\n\r\n";
-        let mut chunk = [0u8; 4];
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        assert!(!*this.done, "polled a RequestHeadDecode after completion");
         loop {
-            let chunk = &mut chunk[self.completion..4];
-            if self.buffer.len() + chunk.len() > self.buffer.capacity() {
+            if this.decoder.tolerate_leading_blank_lines {
+                let blank = this
+                    .buffer
+                    .iter()
+                    .take_while(|&&b| b == b'\r' || b == b'\n')
+                    .count();
+                if blank > 0 {
+                    this.buffer.drain(..blank);
+                    *this.scanned = 0;
+                }
+            }
+            if this.buffer.len() >= HTTP2_PREFACE_PREFIX.len()
+                && this.buffer[..HTTP2_PREFACE_PREFIX.len()] == HTTP2_PREFACE_PREFIX[..]
+            {
+                *this.done = true;
+                let preface = std::mem::take(this.buffer);
+                return Poll::Ready(Ok((
+                    this.transport.take().unwrap(),
+                    DecodedHead::Http2Preface(preface),
+                )));
+            }
+            if let Some(pos) = find_terminator(this.buffer.as_slice(), *this.scanned) {
+                *this.done = true;
+                let leftover = this.buffer.split_off(pos + TERMINATOR.len());
+                return Poll::Ready(
+                    request_head_parse(this.buffer.as_slice(), this.decoder.max_headers)
+                        .map(|request| (this.transport.take().unwrap(), DecodedHead::Http1(Box::new(request), leftover))),
+                );
+            }
+            *this.scanned = this.buffer.len().saturating_sub(TERMINATOR.len() - 1);
+
+            if this.buffer.len() >= this.decoder.max_head_size {
+                *this.done = true;
                 return Poll::Ready(Err(anyhow::Error::msg("request head too long")));
             }
-            let transport = Pin::new(self.transport.as_mut().unwrap().borrow_mut());
-            match transport.poll_read(cx, chunk) {
+            let max_read = this
+                .read_buf
+                .len()
+                .min(this.decoder.max_head_size - this.buffer.len());
+            let transport = Pin::new(this.transport.as_mut().unwrap().borrow_mut());
+            match transport.poll_read(cx, &mut this.read_buf[..max_read]) {
+                Poll::Ready(Ok(0)) => {
+                    *this.done = true;
+                    return Poll::Ready(Err(if this.buffer.is_empty() {
+                        // No bytes read yet for this message: a clean place for the
+                        // transport to be closed, e.g. between pipelined requests.
+                        io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed").into()
+                    } else {
+                        anyhow::Error::msg("connection closed before request head was complete")
+                    }));
+                }
                 Poll::Ready(Ok(n)) => {
-                    let chunk = &chunk[0..n];
-                    self.buffer.extend_from_slice(chunk);
-                    match chunk == &END[self.completion..self.completion + n] {
-                        true => self.completion += n,
-                        false => self.completion = 0,
-                    }
-                    if self.completion == END.len() {
-                        self.completion = usize::MAX;
-                        return Poll::Ready(
-                            request_head_parse(&self.buffer, self.decoder.max_headers)
-                                .map(|request| (self.transport.take().unwrap(), request)),
-                        );
-                    }
+                    this.buffer.extend_from_slice(&this.read_buf[..n]);
                 }
                 Poll::Ready(Err(err)) => {
-                    self.completion = usize::MAX;
+                    *this.done = true;
                     return Poll::Ready(Err(err.into()));
                 }
                 Poll::Pending => return Poll::Pending,
@@ -91,6 +176,15 @@ impl<T: AsyncRead + Unpin, R: BorrowMut<T>> Future for RequestHeadDecode<T, R> {
     }
 }
 
+/// Finds `TERMINATOR` in `buffer`, scanning only from `from` onward. `from` must
+/// already account for the overlap needed to catch a terminator split across reads.
+fn find_terminator(buffer: &[u8], from: usize) -> Option<usize> {
+    buffer[from..]
+        .windows(TERMINATOR.len())
+        .position(|window| window == TERMINATOR)
+        .map(|pos| pos + from)
+}
+
 fn request_head_parse(buffer: &[u8], max_headers: usize) -> anyhow::Result<Request<()>> {
     let mut headers = vec![httparse::EMPTY_HEADER; max_headers];
     let mut parsed_request = httparse::Request::new(&mut headers);
@@ -120,7 +214,7 @@ fn request_head_parse(buffer: &[u8], max_headers: usize) -> anyhow::Result<Reque
 
 #[cfg(test)]
 mod tests {
-    use crate::head::decode::RequestHeadDecoder;
+    use crate::head::decode::{DecodedHead, RequestHeadDecoder};
     use futures_lite::future::block_on;
     use futures_lite::io::Cursor;
     use futures_lite::{AsyncReadExt, StreamExt};
@@ -128,7 +222,14 @@ mod tests {
 
     const INPUT: &[u8] = b"GET / HTTP/1.1\r\nHost: www.example.com\r\nConnection: close\r\n\r\n ";
 
-    async fn check(output: Request<()>, transport: Cursor<&[u8]>) {
+    fn unwrap_http1(decoded: DecodedHead) -> (Request<()>, Vec<u8>) {
+        match decoded {
+            DecodedHead::Http1(request, leftover) => (*request, leftover),
+            DecodedHead::Http2Preface(_) => panic!("expected an HTTP/1 request"),
+        }
+    }
+
+    async fn check(output: Request<()>, leftover: Vec<u8>, transport: Cursor<&[u8]>) {
         assert_eq!(output.version(), Version::HTTP_11);
         assert_eq!(output.method(), Method::GET);
         assert_eq!(
@@ -139,18 +240,20 @@ mod tests {
             output.headers().get("Connection").unwrap().as_bytes(),
             b"close"
         );
-        assert_eq!(transport.bytes().count().await, 1);
+        assert_eq!(leftover, b" ");
+        assert_eq!(transport.bytes().count().await, 0);
     }
 
     #[test]
     fn owned_transport() {
         block_on(async {
             let transport = Cursor::new(INPUT);
-            let (transport, output) = RequestHeadDecoder::default()
+            let (transport, decoded) = RequestHeadDecoder::default()
                 .decode(transport)
                 .await
                 .unwrap();
-            check(output, transport).await;
+            let (output, leftover) = unwrap_http1(decoded);
+            check(output, leftover, transport).await;
         })
     }
 
@@ -158,11 +261,99 @@ mod tests {
     fn referenced_transport() {
         block_on(async {
             let mut transport = Cursor::new(INPUT);
-            let (_, output) = RequestHeadDecoder::default()
+            let (_, decoded) = RequestHeadDecoder::default()
                 .decode_ref(&mut transport)
                 .await
                 .unwrap();
-            check(output, transport).await;
+            let (output, leftover) = unwrap_http1(decoded);
+            check(output, leftover, transport).await;
+        })
+    }
+
+    #[test]
+    fn terminator_split_across_reads() {
+        block_on(async {
+            // The first read stops right after the terminator's leading `\r` (offset
+            // 56), so the rest of the terminator arrives with the second read.
+            let transport = Cursor::new(&INPUT[..57]).chain(Cursor::new(&INPUT[57..]));
+            let (_, decoded) = RequestHeadDecoder::default()
+                .decode(transport)
+                .await
+                .unwrap();
+            let (output, leftover) = unwrap_http1(decoded);
+            assert_eq!(output.method(), Method::GET);
+            assert_eq!(leftover, b" ");
+        })
+    }
+
+    #[test]
+    fn head_too_long_is_rejected() {
+        block_on(async {
+            let mut long_input = b"GET / HTTP/1.1\r\nHost: ".to_vec();
+            long_input.extend(std::iter::repeat_n(b'a', 9000));
+            long_input.extend_from_slice(b"\r\n\r\n");
+            let transport = Cursor::new(long_input.as_slice());
+            let result = RequestHeadDecoder::default().decode(transport).await;
+            assert!(result.is_err());
+        })
+    }
+
+    #[test]
+    fn http2_preface_is_detected() {
+        block_on(async {
+            const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+            let transport = Cursor::new(PREFACE);
+            let (_, decoded) = RequestHeadDecoder::default()
+                .decode(transport)
+                .await
+                .unwrap();
+            match decoded {
+                DecodedHead::Http2Preface(leftover) => assert_eq!(leftover, PREFACE),
+                DecodedHead::Http1(..) => panic!("expected an HTTP/2 preface"),
+            }
+        })
+    }
+
+    #[test]
+    fn clean_eof_before_any_bytes_is_distinguishable() {
+        block_on(async {
+            let transport = Cursor::new(b"".as_slice());
+            let err = RequestHeadDecoder::default().decode(transport).await.unwrap_err();
+            let io_err = err.downcast_ref::<std::io::Error>().expect("expected an io::Error");
+            assert_eq!(io_err.kind(), std::io::ErrorKind::UnexpectedEof);
+        })
+    }
+
+    #[test]
+    fn leading_blank_lines_are_rejected_by_default() {
+        block_on(async {
+            let transport = Cursor::new(b"\r\n\r\nGET / HTTP/1.1\r\n\r\n".as_slice());
+            let result = RequestHeadDecoder::default().decode(transport).await;
+            assert!(result.is_err());
+        })
+    }
+
+    #[test]
+    fn leading_blank_lines_are_tolerated_when_enabled() {
+        block_on(async {
+            let transport = Cursor::new(b"\r\n\r\nGET / HTTP/1.1\r\n\r\n".as_slice());
+            let (_, decoded) = RequestHeadDecoder::default()
+                .tolerate_leading_blank_lines(true)
+                .decode(transport)
+                .await
+                .unwrap();
+            let (output, leftover) = unwrap_http1(decoded);
+            assert_eq!(output.method(), Method::GET);
+            assert!(leftover.is_empty());
+        })
+    }
+
+    #[test]
+    fn eof_mid_head_is_a_plain_error() {
+        block_on(async {
+            let transport = Cursor::new(b"GET / HTTP/1.1\r\n".as_slice());
+            let err = RequestHeadDecoder::default().decode(transport).await.unwrap_err();
+            assert!(err.downcast_ref::<std::io::Error>().is_none());
         })
     }
-}
\ No newline at end of file
+}
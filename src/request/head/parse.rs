@@ -55,18 +55,37 @@ pub struct RequestHeadParse<'a> {
     buffer: Vec<u8>,
     terminator: TerminatorOverlap<'a>,
     max_headers: usize,
+    tolerate_leading_blank_lines: bool,
 }
 
 impl<'a> RequestHeadParse<'a> {
     const END: &'a [u8] = b"\r\n\r\n";
-    pub fn new(max_buffer: usize, max_headers: usize) -> Self {
+    pub fn new(max_buffer: usize, max_headers: usize, tolerate_leading_blank_lines: bool) -> Self {
         Self {
             buffer: Vec::with_capacity(max_buffer),
             terminator: TerminatorOverlap::new(Self::END),
             max_headers,
+            tolerate_leading_blank_lines,
+        }
+    }
+    /// Discards blank lines (bare `\r`/`\n`) preceding the request line, mirroring
+    /// actix's `consume_leading_lines`, so a buggy client or the trailing CRLF of a
+    /// previous pipelined request doesn't surface as a malformed head.
+    fn skip_leading_blank_lines<T: Read>(&mut self, rd: &mut T) -> Result<(), std::io::Error> {
+        let mut byte = [0u8; 1];
+        loop {
+            rd.read_exact(&mut byte)?;
+            if byte[0] != b'\r' && byte[0] != b'\n' {
+                self.terminator.process(&byte);
+                self.buffer.extend_from_slice(&byte);
+                return Ok(());
+            }
         }
     }
     pub fn read_data<T: Read>(&mut self, rd: &mut T) -> Result<usize, std::io::Error> {
+        if self.tolerate_leading_blank_lines {
+            self.skip_leading_blank_lines(rd)?;
+        }
         let mut chunks = [0u8; Self::END.len()];
 	while !self.terminator.done() {
 	    let chunks = self.terminator.max_read_buf(&mut chunks);
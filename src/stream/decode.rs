@@ -0,0 +1,214 @@
+use crate::body::{BodyDecode, BodyDecoder, BodyMode};
+use crate::head::decode::{DecodedHead, RequestHeadDecode, RequestHeadDecoder};
+use bytes::{Bytes, BytesMut};
+use futures_lite::prelude::*;
+use http::Request;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Maximum number of pipelined requests served from one transport by default,
+/// mirroring actix's `MAX_PIPELINED_MESSAGES`.
+const DEFAULT_MAX_PIPELINED: usize = 16;
+
+#[derive(Copy, Clone, Debug)]
+pub struct RequestStreamDecoder {
+    head_decoder: RequestHeadDecoder,
+    max_body_size: usize,
+    max_pipelined: usize,
+}
+
+impl RequestStreamDecoder {
+    pub fn new(head_decoder: RequestHeadDecoder, max_body_size: usize, max_pipelined: usize) -> Self {
+        Self {
+            head_decoder,
+            max_body_size,
+            max_pipelined,
+        }
+    }
+
+    pub fn decode<T: AsyncRead + Unpin>(self, transport: T) -> RequestStreamDecode<T> {
+        RequestStreamDecode {
+            state: State::Head(self.head_decoder.decode(transport)),
+            decoder: self,
+            served: 0,
+        }
+    }
+}
+
+impl Default for RequestStreamDecoder {
+    fn default() -> Self {
+        Self {
+            head_decoder: RequestHeadDecoder::default(),
+            max_body_size: 2 * 1024 * 1024,
+            max_pipelined: DEFAULT_MAX_PIPELINED,
+        }
+    }
+}
+
+enum State<T: AsyncRead + Unpin> {
+    Head(RequestHeadDecode<T, T>),
+    Body {
+        request: Box<Request<()>>,
+        decode: BodyDecode<T, T>,
+        collected: BytesMut,
+    },
+    Done,
+}
+
+/// Decodes successive pipelined HTTP/1 requests - head and body - from a single
+/// transport, carrying bytes read past one request over as the start of the next
+/// rather than discarding them. The head and body decoders keep separate buffers;
+/// the carried-over bytes are copied from one to the other.
+pub struct RequestStreamDecode<T: AsyncRead + Unpin> {
+    state: State<T>,
+    decoder: RequestStreamDecoder,
+    served: usize,
+}
+
+impl<T: AsyncRead + Unpin> Stream for RequestStreamDecode<T> {
+    /// The parsed request paired with its fully collected body.
+    type Item = io::Result<(Request<()>, Bytes)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match std::mem::replace(&mut this.state, State::Done) {
+                State::Done => return Poll::Ready(None),
+                State::Head(mut head) => match Pin::new(&mut head).poll(cx) {
+                    Poll::Pending => {
+                        this.state = State::Head(head);
+                        return Poll::Pending;
+                    }
+                    Poll::Ready(Err(err)) => {
+                        return match err.downcast::<io::Error>() {
+                            Ok(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                                Poll::Ready(None)
+                            }
+                            Ok(err) => Poll::Ready(Some(Err(err))),
+                            Err(err) => Poll::Ready(Some(Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                err,
+                            )))),
+                        };
+                    }
+                    Poll::Ready(Ok((transport, DecodedHead::Http2Preface(_)))) => {
+                        drop(transport);
+                        return Poll::Ready(Some(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "unexpected HTTP/2 preface in a pipelined request stream",
+                        ))));
+                    }
+                    Poll::Ready(Ok((transport, DecodedHead::Http1(request, leftover)))) => {
+                        if this.served >= this.decoder.max_pipelined {
+                            return Poll::Ready(Some(Err(io::Error::other(
+                                "maximum number of pipelined requests exceeded",
+                            ))));
+                        }
+                        this.served += 1;
+                        match BodyMode::from_headers(request.headers()) {
+                            Ok(None) => {
+                                this.state = State::Head(
+                                    this.decoder.head_decoder.decode_with_leading(transport, leftover),
+                                );
+                                return Poll::Ready(Some(Ok((*request, Bytes::new()))));
+                            }
+                            Ok(Some(mode)) => match BodyDecoder::new(mode, this.decoder.max_body_size) {
+                                Ok(body_decoder) => {
+                                    this.state = State::Body {
+                                        request,
+                                        decode: body_decoder.decode(transport, Bytes::from(leftover)),
+                                        collected: BytesMut::new(),
+                                    };
+                                }
+                                Err(err) => return Poll::Ready(Some(Err(err))),
+                            },
+                            Err(err) => return Poll::Ready(Some(Err(err))),
+                        }
+                    }
+                },
+                State::Body {
+                    request,
+                    mut decode,
+                    mut collected,
+                } => match Pin::new(&mut decode).poll_next(cx) {
+                    Poll::Pending => {
+                        this.state = State::Body {
+                            request,
+                            decode,
+                            collected,
+                        };
+                        return Poll::Pending;
+                    }
+                    Poll::Ready(Some(Ok(chunk))) => {
+                        collected.extend_from_slice(&chunk);
+                        this.state = State::Body {
+                            request,
+                            decode,
+                            collected,
+                        };
+                    }
+                    Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                    Poll::Ready(None) => {
+                        let (transport, leftover) = decode.into_parts();
+                        let transport = transport.expect("body decode holds the transport until consumed");
+                        this.state = State::Head(
+                            this.decoder
+                                .head_decoder
+                                .decode_with_leading(transport, leftover.to_vec()),
+                        );
+                        return Poll::Ready(Some(Ok((*request, collected.freeze()))));
+                    }
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::head::decode::RequestHeadDecoder;
+    use futures_lite::future::block_on;
+    use futures_lite::io::Cursor;
+
+    #[test]
+    fn decodes_pipelined_requests_with_and_without_bodies() {
+        block_on(async {
+            let input = b"\
+GET / HTTP/1.1\r\n\r\n\
+POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello\
+PUT /chunked HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nwiki\r\n0\r\n\r\n";
+            let transport = Cursor::new(input.as_slice());
+            let decoder = RequestStreamDecoder::new(RequestHeadDecoder::default(), 1024, 16);
+            let mut stream = decoder.decode(transport);
+
+            let (request, body) = stream.next().await.unwrap().unwrap();
+            assert_eq!(request.uri().path(), "/");
+            assert_eq!(body, Bytes::new());
+
+            let (request, body) = stream.next().await.unwrap().unwrap();
+            assert_eq!(request.uri().path(), "/echo");
+            assert_eq!(body, Bytes::from_static(b"hello"));
+
+            let (request, body) = stream.next().await.unwrap().unwrap();
+            assert_eq!(request.uri().path(), "/chunked");
+            assert_eq!(body, Bytes::from_static(b"wiki"));
+
+            assert!(stream.next().await.is_none());
+        })
+    }
+
+    #[test]
+    fn max_pipelined_is_enforced() {
+        block_on(async {
+            let input = b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n";
+            let transport = Cursor::new(input.as_slice());
+            let decoder = RequestStreamDecoder::new(RequestHeadDecoder::default(), 1024, 1);
+            let mut stream = decoder.decode(transport);
+
+            assert!(stream.next().await.unwrap().is_ok());
+            assert!(stream.next().await.unwrap().is_err());
+        })
+    }
+}